@@ -5,6 +5,9 @@ That's useful for programming exercise sites that take a single source file.
 */
 
 use std::collections::HashSet;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::BufRead;
@@ -12,177 +15,860 @@ use std::io::BufReader;
 use std::io::Write;
 use std::mem::take;
 use std::path::Path;
+use std::path::PathBuf;
 
 extern crate regex;
 use regex::Regex;
 
 const LIBRS_FILENAME: &str = "src/lib.rs";
 
+/// Matches `mod foo;` and `pub mod foo;` (and `pub(crate)`/`pub(super)`
+/// variants), capturing the visibility prefix (if any) and the module name.
+fn mod_re() -> Regex {
+    Regex::new(r"^\s*(pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;\s*$").unwrap()
+}
+
+/// Matches the opening line of an inline `mod foo { ... }` block.
+fn inline_mod_re() -> Regex {
+    Regex::new(r"^\s*(pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*\{\s*$").unwrap()
+}
+
+/// Matches a `#[path = "..."]` attribute, which overrides where the
+/// following `mod` declaration's source file is looked up.
+fn path_attr_re() -> Regex {
+    Regex::new(r#"^\s*#\[path\s*=\s*"([^"]+)"\]\s*$"#).unwrap()
+}
+
+/// Matches an `automod::dir!("...")` call, the marker this bundler
+/// recognizes for glob-including a whole directory of modules.
+fn automod_re() -> Regex {
+    Regex::new(r#"^\s*automod::dir!\(\s*"([^"]+)"\s*\)\s*;\s*$"#).unwrap()
+}
+
+/// Matches a top-level `pub` item declaration (`fn`, `struct`, `enum`,
+/// `trait`, `const`, `static` or `type`), capturing its name. Such a name,
+/// when declared directly in `lib.rs`, ends up hoisted to the bundle's
+/// root the same way a submodule's name does, so `use <crate>::<name>;`
+/// importing it back must be skipped the same way.
+fn item_re() -> Regex {
+    Regex::new(r"^\s*pub(?:\([^)]*\))?\s+(?:fn|struct|enum|trait|const|static|type)\s+(\w+)")
+        .unwrap()
+}
+
+/// Matches a top-level `pub use <tree>;` re-export, capturing its use tree
+/// (everything between `use` and the trailing `;`). A re-export binds
+/// names at the bundle's root the same way a plain item does, so those
+/// names need the same [`item_re`] skip-on-reimport treatment; see
+/// [`use_tree_names`] for how the tree is turned into the bound names.
+fn use_re() -> Regex {
+    Regex::new(r"^\s*pub(?:\([^)]*\))?\s+use\s+(.+);\s*$").unwrap()
+}
+
+/// The names a `use` tree binds at the scope it appears in, e.g.
+/// `["Thing"]` for `sub::Thing`, `["Alias"]` for `sub::Thing as Alias`, or
+/// `["A", "C"]` for `sub::{A, B as C}`. Glob imports (`sub::*`) bind no
+/// fixed name and are skipped.
+fn use_tree_names(tree: &str) -> Vec<String> {
+    let tree = tree.trim();
+    if let (Some(brace_start), Some(brace_end)) = (tree.find('{'), tree.rfind('}')) {
+        return tree[brace_start + 1..brace_end]
+            .split(',')
+            .flat_map(use_tree_names)
+            .collect();
+    }
+    if tree.is_empty() || tree.ends_with('*') {
+        return Vec::new();
+    }
+    let name = match tree.split_once(" as ") {
+        Some((_, alias)) => alias.trim(),
+        None => tree.rsplit("::").next().unwrap_or(tree).trim(),
+    };
+    vec![name.to_string()]
+}
+
+/// Joins a `mod_import` prefix (the `::`-joined path from the crate root,
+/// or `""` at the root) with one more path segment.
+fn join_import(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}::{}", prefix, name)
+    }
+}
+
+/// The file name of `path` for display in a [`BundleError`]'s import chain,
+/// falling back to the full path if it has none.
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// A `[[bin]]` target discovered in a crate's `Cargo.toml`.
+#[derive(Debug, Clone)]
+pub struct BinTarget {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// An I/O failure encountered while bundling, together with the `mod` chain
+/// that led to the offending file, e.g. `lib.rs -> foo -> bar` when
+/// `src/foo/bar.rs` could not be found or opened.
+#[derive(Debug)]
+pub struct BundleError {
+    path: PathBuf,
+    import_chain: Vec<String>,
+    source: io::Error,
+}
+
+impl BundleError {
+    fn new(path: impl Into<PathBuf>, import_chain: Vec<String>, source: io::Error) -> BundleError {
+        BundleError {
+            path: path.into(),
+            import_chain,
+            source,
+        }
+    }
+
+    /// The file the error happened on, or that could not be located.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The chain of `mod` imports from the crate root down to `path`, e.g.
+    /// `["lib.rs", "foo", "bar"]`.
+    pub fn import_chain(&self) -> &[String] {
+        &self.import_chain
+    }
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            self.path.display(),
+            self.import_chain.join(" -> "),
+            self.source
+        )
+    }
+}
+
+impl StdError for BundleError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<BundleError> for io::Error {
+    fn from(err: BundleError) -> io::Error {
+        io::Error::new(err.source.kind(), err)
+    }
+}
+
+/// The handful of lexical contexts a [`Minifier`] tracks across line
+/// boundaries. Everything else (regular strings, char literals, line
+/// comments) is lexed within a single line only: a regular string literal
+/// that spans lines is not tracked past the end of its first line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum MinifyState {
+    #[default]
+    Normal,
+    /// Block comments nest in Rust, so track how many `/*` are still open.
+    BlockComment { depth: u32 },
+    /// Inside a raw string body; `hashes` is how many `#` its `"` closer
+    /// needs, per the opening `r#*"`.
+    RawString { hashes: usize },
+}
+
+/// A small Rust lexer, used only to minify already-valid source: it tracks
+/// just enough lexical state to strip `//` and `/* */` comments and
+/// collapse insignificant whitespace without corrupting string/char
+/// literals, carrying block-comment/raw-string state across lines.
+#[derive(Debug, Clone, Default)]
+struct Minifier {
+    state: MinifyState,
+}
+
+impl Minifier {
+    /// Minifies one line, continuing from whatever lexical state was left
+    /// over from the previous line.
+    fn minify_line(&mut self, line: &str) -> String {
+        let chars: Vec<char> = line.chars().collect();
+        let mut out = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match self.state {
+                MinifyState::BlockComment { depth } => {
+                    if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                        self.state = MinifyState::BlockComment { depth: depth + 1 };
+                        i += 2;
+                    } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                        self.state = if depth > 1 {
+                            MinifyState::BlockComment { depth: depth - 1 }
+                        } else {
+                            MinifyState::Normal
+                        };
+                        i += 2;
+                    } else {
+                        i += 1;
+                    }
+                }
+                MinifyState::RawString { hashes } => {
+                    out.push(chars[i]);
+                    if chars[i] == '"' && Self::raw_string_closes(&chars, i, hashes) {
+                        for _ in 0..hashes {
+                            i += 1;
+                            out.push(chars[i]);
+                        }
+                        self.state = MinifyState::Normal;
+                    }
+                    i += 1;
+                }
+                MinifyState::Normal => {
+                    if chars[i] == '/' && chars.get(i + 1) == Some(&'/') {
+                        break;
+                    } else if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                        self.state = MinifyState::BlockComment { depth: 1 };
+                        i += 2;
+                    } else if let Some((len, hashes)) = Self::raw_string_prefix(&chars, i) {
+                        for k in 0..len {
+                            out.push(chars[i + k]);
+                        }
+                        i += len;
+                        self.state = MinifyState::RawString { hashes };
+                    } else if chars[i] == '"' {
+                        out.push(chars[i]);
+                        i += 1;
+                        while i < chars.len() {
+                            let c = chars[i];
+                            out.push(c);
+                            i += 1;
+                            if c == '\\' {
+                                if i < chars.len() {
+                                    out.push(chars[i]);
+                                    i += 1;
+                                }
+                                continue;
+                            }
+                            if c == '"' {
+                                break;
+                            }
+                        }
+                    } else if chars[i] == '\'' {
+                        match Self::char_literal_len(&chars, i) {
+                            Some(len) => {
+                                for k in 0..len {
+                                    out.push(chars[i + k]);
+                                }
+                                i += len;
+                            }
+                            None => {
+                                out.push(chars[i]);
+                                i += 1;
+                            }
+                        }
+                    } else if chars[i].is_whitespace() {
+                        if out.chars().last().is_some_and(|c| !c.is_whitespace()) {
+                            out.push(' ');
+                        }
+                        i += 1;
+                    } else {
+                        out.push(chars[i]);
+                        i += 1;
+                    }
+                }
+            }
+        }
+        out.trim_end().to_string()
+    }
+
+    /// Whether `(b)?r#*"` starts at `chars[i]`, returning the prefix's
+    /// length (including its opening quote) and how many `#` its closing
+    /// quote needs.
+    fn raw_string_prefix(chars: &[char], i: usize) -> Option<(usize, usize)> {
+        let mut j = i;
+        if chars.get(j) == Some(&'b') {
+            j += 1;
+        }
+        if chars.get(j) != Some(&'r') {
+            return None;
+        }
+        j += 1;
+        let hash_start = j;
+        while chars.get(j) == Some(&'#') {
+            j += 1;
+        }
+        if chars.get(j) == Some(&'"') {
+            Some((j - i + 1, j - hash_start))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the `"` at `chars[i]` is followed by the `hashes` `#`s a
+    /// raw string opened with `hashes` hashes needs to close.
+    fn raw_string_closes(chars: &[char], i: usize, hashes: usize) -> bool {
+        (1..=hashes).all(|k| chars.get(i + k) == Some(&'#'))
+    }
+
+    /// The length of the char literal starting at `chars[i]` (a `'`), or
+    /// `None` if this is actually a lifetime/label's leading apostrophe.
+    fn char_literal_len(chars: &[char], i: usize) -> Option<usize> {
+        if chars.get(i + 1) == Some(&'\\') {
+            if chars.get(i + 2) == Some(&'u') && chars.get(i + 3) == Some(&'{') {
+                let mut j = i + 4;
+                while chars.get(j).is_some_and(|c| *c != '}') {
+                    j += 1;
+                }
+                if chars.get(j) != Some(&'}') {
+                    return None;
+                }
+                (chars.get(j + 1) == Some(&'\'')).then_some(j + 2 - i)
+            } else if chars.get(i + 2) == Some(&'x') {
+                (chars.get(i + 5) == Some(&'\'')).then_some(6)
+            } else {
+                (chars.get(i + 3) == Some(&'\'')).then_some(4)
+            }
+        } else if chars.get(i + 1).is_some() && chars.get(i + 2) == Some(&'\'') {
+            Some(3)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct Bundler<'a> {
-    binrs_filename: &'a Path,
-    bundle_filename: &'a Path,
-    librs_filename: &'a Path,
+pub struct Bundler {
+    binrs_filename: PathBuf,
+    bundle_filename: PathBuf,
+    librs_filename: PathBuf,
     comment_re: Regex,
     warn_re: Regex,
-    _crate_name: &'a str,
+    mod_re: Regex,
+    inline_mod_re: Regex,
+    path_attr_re: Regex,
+    automod_re: Regex,
+    item_re: Regex,
+    use_re: Regex,
+    crate_path_re: Option<Regex>,
+    crate_name: String,
     skip_use: HashSet<String>,
-    minify_re: Option<Regex>,
+    minifier: Option<Minifier>,
+    bins: Vec<BinTarget>,
+    manifest_dir: PathBuf,
 }
 
-impl<'a> Bundler<'a> {
-    pub fn new(binrs_filename: &'a Path, bundle_filename: &'a Path) -> Bundler<'a> {
+impl Bundler {
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(binrs_filename: P, bundle_filename: Q) -> Bundler {
         Bundler {
-            binrs_filename,
-            bundle_filename,
-            librs_filename: Path::new(LIBRS_FILENAME),
+            binrs_filename: binrs_filename.as_ref().to_path_buf(),
+            bundle_filename: bundle_filename.as_ref().to_path_buf(),
+            librs_filename: PathBuf::from(LIBRS_FILENAME),
             comment_re: Regex::new(r"^\s*//").unwrap(),
             warn_re: Regex::new(r"^\s*#!\[warn\(.*").unwrap(),
-            _crate_name: "",
+            mod_re: mod_re(),
+            inline_mod_re: inline_mod_re(),
+            path_attr_re: path_attr_re(),
+            automod_re: automod_re(),
+            item_re: item_re(),
+            use_re: use_re(),
+            crate_path_re: None,
+            crate_name: String::new(),
             skip_use: HashSet::new(),
-            minify_re: None,
+            minifier: None,
+            bins: Vec::new(),
+            manifest_dir: PathBuf::from("."),
         }
     }
 
+    /// Builds a `Bundler` by reading `manifest_path` (a crate's `Cargo.toml`):
+    /// the crate name, the `[lib]` path (honoring a custom `path`), and every
+    /// `[[bin]]` target are discovered automatically, so none of them need to
+    /// be wired up by hand.
+    ///
+    /// If the manifest declares exactly one binary, it is selected as the
+    /// bundling target right away; with zero or several binaries, use
+    /// [`Bundler::bins`] and [`Bundler::select_bin`] (or [`Bundler::run_all`])
+    /// to pick which one(s) to bundle.
+    pub fn from_cargo_toml<P: AsRef<Path>>(manifest_path: P) -> io::Result<Bundler> {
+        let manifest_path = manifest_path.as_ref();
+        let manifest_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+        let manifest = parse_cargo_toml(manifest_path)?;
+
+        let mut bundler = Bundler {
+            binrs_filename: PathBuf::new(),
+            bundle_filename: manifest_dir.join("bundled.rs"),
+            librs_filename: manifest
+                .lib_path
+                .unwrap_or_else(|| manifest_dir.join("src").join("lib.rs")),
+            comment_re: Regex::new(r"^\s*//").unwrap(),
+            warn_re: Regex::new(r"^\s*#!\[warn\(.*").unwrap(),
+            mod_re: mod_re(),
+            inline_mod_re: inline_mod_re(),
+            path_attr_re: path_attr_re(),
+            automod_re: automod_re(),
+            item_re: item_re(),
+            use_re: use_re(),
+            crate_path_re: None,
+            crate_name: manifest.crate_name,
+            skip_use: HashSet::new(),
+            minifier: None,
+            bins: manifest.bins,
+            manifest_dir: manifest_dir.to_path_buf(),
+        };
+
+        if let [only_bin] = bundler.bins.as_slice() {
+            bundler.binrs_filename = only_bin.path.clone();
+            bundler.bundle_filename = manifest_dir.join(format!("{}_bundled.rs", only_bin.name));
+        }
+
+        Ok(bundler)
+    }
+
+    /// The `[[bin]]` targets discovered by [`Bundler::from_cargo_toml`].
+    pub fn bins(&self) -> &[BinTarget] {
+        &self.bins
+    }
+
+    /// Selects which discovered `[[bin]]` target `run()` should bundle.
+    pub fn select_bin(&mut self, name: &str) -> io::Result<()> {
+        let bin = self
+            .bins
+            .iter()
+            .find(|bin| bin.name == name)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no [[bin]] target named `{}`", name),
+                )
+            })?;
+        self.binrs_filename = bin.path.clone();
+        self.bundle_filename = self.manifest_dir.join(format!("{}_bundled.rs", bin.name));
+        Ok(())
+    }
+
+    /// Bundles every discovered `[[bin]]` target, one file per binary, into
+    /// `out_dir/<bin name>.rs`.
+    pub fn run_all<P: AsRef<Path>>(&mut self, out_dir: P) -> io::Result<()> {
+        let out_dir = out_dir.as_ref();
+        for bin in self.bins.clone() {
+            self.binrs_filename = bin.path;
+            self.bundle_filename = out_dir.join(format!("{}.rs", bin.name));
+            self.try_run()?;
+        }
+        Ok(())
+    }
+
     pub fn minify_set(&mut self, enable: bool) {
-        self.minify_re = if enable {
-            Some(Regex::new(r"^\s*(?P<contents>.*)\s*$").unwrap())
+        self.minifier = if enable {
+            Some(Minifier::default())
         } else {
             None
         };
     }
 
-    pub fn crate_name(&mut self, name: &'a str) {
-        self._crate_name = name;
+    pub fn crate_name(&mut self, name: &str) {
+        self.crate_name = name.to_string();
     }
 
+    /// Thin, panicking wrapper around [`Bundler::try_run`] for callers (e.g.
+    /// a `build.rs`) that would rather abort the build than handle a
+    /// [`BundleError`] themselves.
     pub fn run(&mut self) {
-        let mut o = File::create(&self.bundle_filename)
-            .unwrap_or_else(|_| panic!("error creating {}", &self.bundle_filename.display()));
-        self.binrs(&mut o).unwrap_or_else(|_| {
-            panic!(
-                "error creating bundle {} for {}",
-                self.bundle_filename.display(),
-                self.binrs_filename.display()
+        self.try_run().unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Bundles `binrs_filename` into `bundle_filename`, returning a
+    /// [`BundleError`] (carrying the offending path and `mod` import chain)
+    /// instead of panicking on the first I/O failure.
+    pub fn try_run(&mut self) -> Result<(), BundleError> {
+        self.skip_use.clear();
+        let mut o = File::create(&self.bundle_filename).map_err(|e| {
+            BundleError::new(
+                self.bundle_filename.clone(),
+                vec![display_name(&self.bundle_filename)],
+                e,
             )
-        });
+        })?;
+        self.binrs(&mut o)?;
         println!("rerun-if-changed={}", self.bundle_filename.display());
+        Ok(())
     }
 
     /// From the file that has the main() function, expand "extern
-    /// crate <_crate_name>" into lib.rs contents, and smartly skips
-    /// "use <_crate_name>::" lines.
-    fn binrs(&mut self, mut o: &mut File) -> Result<(), io::Error> {
-        let bin_fd = File::open(self.binrs_filename)?;
+    /// crate <crate_name>" into lib.rs contents, and smartly skips
+    /// "use <crate_name>::" lines.
+    fn binrs(&mut self, mut o: &mut File) -> Result<(), BundleError> {
+        let binrs_filename = self.binrs_filename.clone();
+        let bin_chain = vec![display_name(&binrs_filename)];
+        let bin_err = |e: io::Error| BundleError::new(binrs_filename.clone(), bin_chain.clone(), e);
+
+        let bin_fd = File::open(&self.binrs_filename).map_err(bin_err)?;
         let mut bin_reader = BufReader::new(&bin_fd);
 
         let extcrate_re =
-            Regex::new(format!(r"^extern crate {};$", String::from(self._crate_name)).as_str())
-                .unwrap();
+            Regex::new(format!(r"^extern crate {};$", self.crate_name).as_str()).unwrap();
         let usecrate_re =
-            Regex::new(format!(r"^use {}::(.*);$", String::from(self._crate_name)).as_str())
-                .unwrap();
+            Regex::new(format!(r"^use {}::(.*);$", self.crate_name).as_str()).unwrap();
 
         let mut line = String::new();
-        while bin_reader.read_line(&mut line).unwrap() > 0 {
+        loop {
+            let n = bin_reader.read_line(&mut line).map_err(bin_err)?;
+            if n == 0 {
+                break;
+            }
             let line = take(&mut line); // take string for next loop
             let line = line.trim_end();
-            if self.comment_re.is_match(&line) || self.warn_re.is_match(&line) {
-            } else if extcrate_re.is_match(&line) {
+            if self.comment_re.is_match(line) || self.warn_re.is_match(line) {
+            } else if extcrate_re.is_match(line) {
                 self.librs(o)?;
-            } else if let Some(cap) = usecrate_re.captures(&line) {
+            } else if let Some(cap) = usecrate_re.captures(line) {
                 let moduse = cap.get(1).unwrap().as_str();
                 if !self.skip_use.contains(moduse) {
-                    writeln!(&mut o, "use {};", moduse)?;
+                    writeln!(&mut o, "use {};", moduse).map_err(bin_err)?;
                 }
             } else {
-                self.write_line(&mut o, &line)?;
+                self.write_line(&mut o, line).map_err(bin_err)?;
             }
         }
         Ok(())
     }
 
-    /// Expand lib.rs contents and "pub mod <>;" lines.
-    fn librs(&mut self, mut o: &mut File) -> Result<(), io::Error> {
-        let lib_fd = File::open(self.librs_filename).expect("could not open lib.rs");
-        let mut lib_reader = BufReader::new(&lib_fd);
+    /// Expand lib.rs contents and its `mod`/`pub mod` lines.
+    fn librs(&mut self, o: &mut File) -> Result<(), BundleError> {
+        self.crate_path_re = Some(
+            Regex::new(&format!(
+                r"\b(?:crate|{})::",
+                regex::escape(&self.crate_name)
+            ))
+            .unwrap(),
+        );
+        let librs_filename = self.librs_filename.clone();
+        self.expand_file(o, &librs_filename, "")
+    }
+
+    /// Builds the [`BundleError`] import chain rooted at `lib.rs` (or
+    /// whatever the configured lib path is actually named) for an error
+    /// that happened `mod_import`-deep into the module tree.
+    fn chain_from_lib(&self, mod_import: &str) -> Vec<String> {
+        std::iter::once(display_name(&self.librs_filename))
+            .chain(
+                mod_import
+                    .split("::")
+                    .filter(|s| !s.is_empty())
+                    .map(String::from),
+            )
+            .collect()
+    }
 
-        let mod_re = Regex::new(r"^\s*pub mod (.+);$").unwrap();
+    /// Expand the contents of `filename`, recursively following `mod`/`pub
+    /// mod` declarations into their own source files, inlining `mod foo {
+    /// ... }` blocks in place (their own `mod`/`automod::dir!` lines are
+    /// expanded and their `crate::` paths rewritten), and tracking the "use <>;" imports that have
+    /// to be skipped under `mod_import` (the `::`-joined path leading to
+    /// `filename` from the crate root, or `""` for lib.rs itself).
+    fn expand_file(
+        &mut self,
+        mut o: &mut File,
+        filename: &Path,
+        mod_import: &str,
+    ) -> Result<(), BundleError> {
+        let chain = self.chain_from_lib(mod_import);
+        let err = |e: io::Error| BundleError::new(filename.to_path_buf(), chain.clone(), e);
 
+        let fd = File::open(filename).map_err(err)?;
+        let mut reader = BufReader::new(fd);
+        // `mod.rs`/`lib.rs` own the directory they live in, so their
+        // submodules resolve alongside them; any other file `foo.rs` owns
+        // a same-named sibling directory `foo/` instead.
+        let dir = match filename.file_name().and_then(|n| n.to_str()) {
+            Some("mod.rs") | Some("lib.rs") => filename
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .to_path_buf(),
+            _ => filename.with_extension(""),
+        };
+        let nest_depth = if mod_import.is_empty() {
+            0
+        } else {
+            mod_import.matches("::").count() + 1
+        };
+
+        let mut pending_path: Option<String> = None;
         let mut line = String::new();
-        while lib_reader.read_line(&mut line).unwrap() > 0 {
+        loop {
+            let n = reader.read_line(&mut line).map_err(err)?;
+            if n == 0 {
+                break;
+            }
             let line = take(&mut line); // take string for next loop
             let line = line.trim_end();
-            if self.comment_re.is_match(&line) || self.warn_re.is_match(&line) {
-            } else if let Some(cap) = mod_re.captures(&line) {
-                let modname = cap.get(1).unwrap().as_str();
+            if self.comment_re.is_match(line) || self.warn_re.is_match(line) {
+            } else if let Some(cap) = self.path_attr_re.captures(line) {
+                pending_path = Some(cap[1].to_string());
+            } else if let Some(cap) = self.mod_re.captures(line) {
+                let vis = cap.get(1).map_or("", |m| m.as_str());
+                let modname = cap[2].to_string();
+                let modfile = match pending_path.take() {
+                    Some(path) => dir.join(path),
+                    None => self.resolve_mod_file(&dir, &modname, mod_import)?,
+                };
                 if modname != "tests" {
-                    self.usemod(o, modname, modname, modname)?;
+                    let submod_import = join_import(mod_import, &modname);
+                    self.skip_use.insert(submod_import.clone());
+                    writeln!(&mut o, "{}mod {} {{", vis, modname).map_err(err)?;
+                    self.expand_file(o, &modfile, &submod_import)?;
+                    writeln!(&mut o, "}}").map_err(err)?;
+                }
+            } else if let Some(cap) = self.automod_re.captures(line) {
+                let target_dir = self.manifest_dir.join(&cap[1]);
+                self.automod_dir(o, &target_dir, mod_import)?;
+            } else if let Some(cap) = self.inline_mod_re.captures(line) {
+                let vis = cap.get(1).map_or("", |m| m.as_str());
+                let modname = cap[2].to_string();
+                let skip = modname == "tests";
+                let submod_import = join_import(mod_import, &modname);
+                if !skip {
+                    self.skip_use.insert(submod_import.clone());
+                    writeln!(&mut o, "{}mod {} {{", vis, modname).map_err(err)?;
+                }
+                // File-based `mod foo;` resolves relative to the module path,
+                // not to how the enclosing `mod {modname} { ... }` itself was
+                // declared, so it follows `dir`/`mod_import` joined with
+                // `modname` the same way a same-named file-based module's own
+                // submodules would. `automod::dir!(...)` takes its directory
+                // relative to the manifest dir, as it does at top level; only
+                // its import path follows `mod_import` joined with `modname`.
+                let inline_dir = dir.join(&modname);
+                let mut brace_depth = 1i32;
+                let mut body = String::new();
+                while brace_depth > 0 {
+                    body.clear();
+                    if reader.read_line(&mut body).map_err(err)? == 0 {
+                        break;
+                    }
+                    let body_line = body.trim_end();
+                    brace_depth += body_line.matches('{').count() as i32;
+                    brace_depth -= body_line.matches('}').count() as i32;
+                    if brace_depth > 0 && !skip {
+                        if let Some(cap) = self.automod_re.captures(body_line) {
+                            let target_dir = self.manifest_dir.join(&cap[1]);
+                            self.automod_dir(o, &target_dir, &submod_import)?;
+                        } else if let Some(cap) = self.mod_re.captures(body_line) {
+                            let sub_vis = cap.get(1).map_or("", |m| m.as_str());
+                            let submodname = cap[2].to_string();
+                            if submodname != "tests" {
+                                let modfile = self.resolve_mod_file(
+                                    &inline_dir,
+                                    &submodname,
+                                    &submod_import,
+                                )?;
+                                let nested_import = join_import(&submod_import, &submodname);
+                                self.skip_use.insert(nested_import.clone());
+                                writeln!(&mut o, "{}mod {} {{", sub_vis, submodname)
+                                    .map_err(err)?;
+                                self.expand_file(o, &modfile, &nested_import)?;
+                                writeln!(&mut o, "}}").map_err(err)?;
+                            }
+                        } else {
+                            let body_line = self.rewrite_crate_path(body_line, nest_depth + 1);
+                            self.write_line(&mut o, &body_line).map_err(err)?;
+                        }
+                    }
+                }
+                if !skip {
+                    writeln!(&mut o, "}}").map_err(err)?;
                 }
             } else {
-                self.write_line(&mut o, &line)?;
+                pending_path = None;
+                if mod_import.is_empty() {
+                    if let Some(cap) = self.item_re.captures(line) {
+                        self.skip_use.insert(cap[1].to_string());
+                    } else if let Some(cap) = self.use_re.captures(line) {
+                        self.skip_use.extend(use_tree_names(&cap[1]));
+                    }
+                }
+                let line = self.rewrite_crate_path(line, nest_depth);
+                self.write_line(&mut o, &line).map_err(err)?;
             }
         }
         Ok(())
     }
 
-    /// Called to expand random .rs files from lib.rs. It recursivelly
-    /// expands further "pub mod <>;" lines and updates the list of
-    /// "use <>;" lines that have to be skipped.
-    fn usemod(
+    /// Rewrites `crate::`/`<crate_name>::`-rooted paths emitted from the
+    /// library's own sources so they still resolve once this line ends up
+    /// `depth` `mod {}` wrappers deep in the bundle: each wrapper is one
+    /// more hop away from the bundle's root, so each needs one extra
+    /// `super::` to get back to it. `self::`/`super::` paths need no such
+    /// rewriting: expand_file mirrors the original module tree one-to-one,
+    /// so they already point at the same place relative to the wrapping.
+    fn rewrite_crate_path(&self, line: &str, depth: usize) -> String {
+        match &self.crate_path_re {
+            Some(re) => re.replace_all(line, "super::".repeat(depth)).into_owned(),
+            None => line.to_string(),
+        }
+    }
+
+    /// Glob-includes every `.rs` file directly under `dir` (automod-style),
+    /// each as its own `pub mod <filename>`, sorted by filename and skipping
+    /// `mod.rs`/`lib.rs`; subdirectories recurse as `pub mod <dirname>`.
+    fn automod_dir(
         &mut self,
-        mut o: &mut File,
-        mod_name: &str,
-        mod_path: &str,
+        o: &mut File,
+        dir: &Path,
         mod_import: &str,
-    ) -> Result<(), io::Error> {
-        let mod_filenames0 = vec![
-            format!("src/{}.rs", mod_path),
-            format!("src/{}/mod.rs", mod_path),
-        ];
-        let mod_fd = mod_filenames0
-            .iter()
-            .map(|fn0| {
-                let mod_filename = Path::new(&fn0);
-                File::open(mod_filename)
-            })
-            .find(|fd| fd.is_ok());
-        assert!(mod_fd.is_some(), "could not find file for module");
-        let mut mod_reader = BufReader::new(mod_fd.unwrap().unwrap());
+    ) -> Result<(), BundleError> {
+        let chain = self.chain_from_lib(mod_import);
+        let err = |e: io::Error| BundleError::new(dir.to_path_buf(), chain.clone(), e);
 
-        let mod_re = Regex::new(r"^\s*pub mod (.+);$").unwrap();
+        let mut entries: Vec<_> = fs::read_dir(dir)
+            .map_err(err)?
+            .collect::<Result<_, io::Error>>()
+            .map_err(err)?;
+        entries.sort_by_key(|entry| entry.file_name());
 
-        let mut line = String::new();
-
-        writeln!(&mut o, "pub mod {} {{", mod_name)?;
-        self.skip_use.insert(String::from(mod_import));
-
-        while mod_reader.read_line(&mut line).unwrap() > 0 {
-            let line = take(&mut line); // take string for next loop
-            let line = line.trim_end();
-            if self.comment_re.is_match(&line) || self.warn_re.is_match(&line) {
-            } else if let Some(cap) = mod_re.captures(&line) {
-                let submodname = cap.get(1).unwrap().as_str();
-                if submodname != "tests" {
-                    let submodfile = format!("{}/{}", mod_path, submodname);
-                    let submodimport = format!("{}::{}", mod_import, submodname);
-                    self.usemod(o, submodname, submodfile.as_str(), submodimport.as_str())?;
+        for entry in entries {
+            let path = entry.path();
+            if entry.file_type().map_err(err)?.is_dir() {
+                let modname = entry.file_name().to_string_lossy().into_owned();
+                if modname == "tests" {
+                    continue;
                 }
-            } else {
-                self.write_line(&mut o, &line)?;
+                let submod_import = join_import(mod_import, &modname);
+                self.skip_use.insert(submod_import.clone());
+                writeln!(&mut *o, "pub mod {} {{", modname).map_err(err)?;
+                self.automod_dir(o, &path, &submod_import)?;
+                writeln!(&mut *o, "}}").map_err(err)?;
+            } else if path.extension().and_then(|ext| ext.to_str()) == Some("rs") {
+                let modname = path.file_stem().unwrap().to_string_lossy().into_owned();
+                if modname == "mod" || modname == "lib" || modname == "tests" {
+                    continue;
+                }
+                let submod_import = join_import(mod_import, &modname);
+                self.skip_use.insert(submod_import.clone());
+                writeln!(&mut *o, "pub mod {} {{", modname).map_err(err)?;
+                self.expand_file(o, &path, &submod_import)?;
+                writeln!(&mut *o, "}}").map_err(err)?;
             }
         }
-
-        writeln!(&mut o, "}}")?;
-
         Ok(())
     }
 
-    fn write_line(&self, mut o: &mut File, line: &str) -> Result<(), io::Error> {
-        if let Some(ref minify_re) = self.minify_re {
-            writeln!(&mut o, "{}", minify_re.replace_all(line, "$contents"))
+    /// Probes the default locations rustc would use for `mod <modname>;`
+    /// declared in a file that lives in `dir`: `dir/<modname>.rs` or
+    /// `dir/<modname>/mod.rs`.
+    fn resolve_mod_file(
+        &self,
+        dir: &Path,
+        modname: &str,
+        mod_import: &str,
+    ) -> Result<PathBuf, BundleError> {
+        let candidates = [
+            dir.join(format!("{}.rs", modname)),
+            dir.join(modname).join("mod.rs"),
+        ];
+        candidates
+            .iter()
+            .find(|candidate| candidate.is_file())
+            .cloned()
+            .ok_or_else(|| {
+                let source = io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "could not find file for module `{}` in {}",
+                        modname,
+                        dir.display()
+                    ),
+                );
+                let mut chain = self.chain_from_lib(mod_import);
+                chain.push(modname.to_string());
+                BundleError::new(&candidates[0], chain, source)
+            })
+    }
+
+    fn write_line(&mut self, mut o: &mut File, line: &str) -> Result<(), io::Error> {
+        if let Some(ref mut minifier) = self.minifier {
+            let minified = minifier.minify_line(line);
+            if minified.is_empty() {
+                return Ok(());
+            }
+            writeln!(&mut o, "{}", minified)
         } else {
             writeln!(&mut o, "{}", line)
         }
     }
 }
+
+struct CargoManifest {
+    crate_name: String,
+    lib_path: Option<PathBuf>,
+    bins: Vec<BinTarget>,
+}
+
+/// A tiny, good-enough `Cargo.toml` reader: it only understands the
+/// `[package]`, `[lib]` and `[[bin]]` tables and plain `key = "value"`
+/// entries, which is all `Bundler::from_cargo_toml` needs.
+fn parse_cargo_toml(manifest_path: &Path) -> io::Result<CargoManifest> {
+    let manifest_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+    let contents = fs::read_to_string(manifest_path)?;
+
+    let section_re = Regex::new(r"^\[+\s*([^\]]+?)\s*\]+$").unwrap();
+    let kv_re = Regex::new(r#"^(\w+)\s*=\s*"([^"]*)"\s*$"#).unwrap();
+
+    let mut crate_name = None;
+    let mut lib_path = None;
+    let mut bins = Vec::new();
+    let mut section = String::new();
+    let mut bin_name: Option<String> = None;
+    let mut bin_path: Option<String> = None;
+
+    let flush_bin = |bins: &mut Vec<BinTarget>, name: Option<String>, path: Option<String>| {
+        if let Some(name) = name {
+            let path = path.unwrap_or_else(|| format!("src/bin/{}.rs", name));
+            bins.push(BinTarget {
+                name,
+                path: manifest_dir.join(path),
+            });
+        }
+    };
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if let Some(cap) = section_re.captures(line) {
+            if section == "bin" {
+                flush_bin(&mut bins, bin_name.take(), bin_path.take());
+            }
+            section = cap[1].to_string();
+            continue;
+        }
+        let Some(cap) = kv_re.captures(line) else {
+            continue;
+        };
+        let key = &cap[1];
+        let value = &cap[2];
+        match (section.as_str(), key) {
+            ("package", "name") => crate_name = Some(value.replace('-', "_")),
+            ("lib", "path") => lib_path = Some(manifest_dir.join(value)),
+            ("bin", "name") => bin_name = Some(value.to_string()),
+            ("bin", "path") => bin_path = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    if section == "bin" {
+        flush_bin(&mut bins, bin_name.take(), bin_path.take());
+    }
+
+    let crate_name = crate_name.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: missing [package] name", manifest_path.display()),
+        )
+    })?;
+
+    Ok(CargoManifest {
+        crate_name,
+        lib_path,
+        bins,
+    })
+}
@@ -0,0 +1,13 @@
+pub mod util {
+pub fn shout(s: &str) -> String {
+    format!("{}!", s)
+}
+}
+
+pub fn greet() -> String {
+    util::shout("hello")
+}
+
+fn main() {
+    println!("{} {}", greet(), util::shout("hi"));
+}
@@ -0,0 +1,36 @@
+mod sub {
+pub struct Thing(pub i32);
+}
+pub use sub::Thing;
+
+pub mod outer {
+pub mod inner {
+pub fn value() -> i32 {
+    42
+}
+}
+}
+
+pub mod probs {
+pub mod one {
+pub fn one() -> i32 {
+    1
+}
+}
+pub mod two {
+pub fn two() -> i32 {
+    2
+}
+}
+}
+
+fn main() {
+    let t = Thing(5);
+    println!(
+        "{} {} {} {}",
+        t.0,
+        outer::inner::value(),
+        probs::one::one(),
+        probs::two::two()
+    );
+}
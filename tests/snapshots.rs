@@ -0,0 +1,188 @@
+//! Snapshot tests: bundles each fixture crate under `tests/source/<case>/`
+//! and compares the result against the checked-in
+//! `tests/expected/<case>.rs`.
+//!
+//! Set the `BLESS` environment variable to regenerate the expected files
+//! from the current output instead of asserting against them.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use sourcebundler::Bundler;
+
+fn source_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/source")
+}
+
+fn expected_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/expected")
+}
+
+fn cases() -> Vec<String> {
+    let mut cases: Vec<String> = fs::read_dir(source_dir())
+        .expect("tests/source should exist")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    cases.sort();
+    cases
+}
+
+#[test]
+fn snapshots_match() {
+    let bless = env::var_os("BLESS").is_some();
+    let out_dir = env::temp_dir().join(format!(
+        "sourcebundler-snapshot-tests-{}",
+        std::process::id()
+    ));
+    fs::create_dir_all(&out_dir).expect("create snapshot output dir");
+
+    let failures: Vec<String> = cases()
+        .iter()
+        .filter_map(|case| run_case(case, &out_dir, bless).err())
+        .collect();
+
+    let _ = fs::remove_dir_all(&out_dir);
+
+    if !failures.is_empty() {
+        panic!(
+            "{} snapshot(s) mismatched:\n\n{}",
+            failures.len(),
+            failures.join("\n\n")
+        );
+    }
+}
+
+fn run_case(case: &str, out_dir: &Path, bless: bool) -> Result<(), String> {
+    let manifest = source_dir().join(case).join("Cargo.toml");
+    let mut bundler = Bundler::from_cargo_toml(&manifest)
+        .map_err(|e| format!("{}: failed to read manifest: {}", case, e))?;
+    bundler
+        .run_all(out_dir)
+        .map_err(|e| format!("{}: failed to bundle: {}", case, e))?;
+
+    let bin_name = &bundler
+        .bins()
+        .first()
+        .unwrap_or_else(|| panic!("{}: fixture has no [[bin]] target", case))
+        .name;
+    let actual = fs::read_to_string(out_dir.join(format!("{}.rs", bin_name)))
+        .map_err(|e| format!("{}: failed to read bundled output: {}", case, e))?;
+
+    check_compiles(case, out_dir, bin_name, &actual)?;
+
+    let expected_path = expected_dir().join(format!("{}.rs", case));
+    if bless {
+        fs::write(&expected_path, &actual)
+            .unwrap_or_else(|e| panic!("{}: failed to bless expected output: {}", case, e));
+        return Ok(());
+    }
+
+    let expected = fs::read_to_string(&expected_path).map_err(|e| {
+        format!(
+            "{}: missing expected output at {} ({}); run with BLESS=1 to create it",
+            case,
+            expected_path.display(),
+            e
+        )
+    })?;
+
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("{}:\n{}", case, unified_diff(&expected, &actual)))
+    }
+}
+
+/// Feeds `source` through `rustc` as a compile-only sanity check: a text
+/// diff against the expected snapshot can't catch a bundle that no longer
+/// compiles (e.g. a hoisted item colliding with a re-imported `use`), so
+/// every case must also build standalone before its snapshot is trusted.
+fn check_compiles(case: &str, out_dir: &Path, bin_name: &str, source: &str) -> Result<(), String> {
+    let check_bin = out_dir.join(format!("{}_check", bin_name));
+    let mut child = Command::new("rustc")
+        .args(["--edition", "2021", "--crate-type", "bin", "-", "-o"])
+        .arg(&check_bin)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: failed to spawn rustc: {}", case, e))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin was piped")
+        .write_all(source.as_bytes())
+        .map_err(|e| format!("{}: failed to feed rustc stdin: {}", case, e))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("{}: failed to wait for rustc: {}", case, e))?;
+    let _ = fs::remove_file(&check_bin);
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: bundled output does not compile:\n{}",
+            case,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// A minimal unified-diff-style renderer (no hunk headers or context
+/// folding), good enough for the small bundled-source snapshots these
+/// tests compare: a common LCS line is printed plain, a removed expected
+/// line is prefixed `-`, an added actual line is prefixed `+`.
+fn unified_diff(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            out.push_str("  ");
+            out.push_str(a[i]);
+            out.push('\n');
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push_str("- ");
+            out.push_str(a[i]);
+            out.push('\n');
+            i += 1;
+        } else {
+            out.push_str("+ ");
+            out.push_str(b[j]);
+            out.push('\n');
+            j += 1;
+        }
+    }
+    for line in &a[i..] {
+        out.push_str("- ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    for line in &b[j..] {
+        out.push_str("+ ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
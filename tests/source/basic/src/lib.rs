@@ -0,0 +1,6 @@
+mod tests;
+pub mod util;
+
+pub fn greet() -> String {
+    util::shout("hello")
+}
@@ -0,0 +1,7 @@
+extern crate basic;
+use basic::greet;
+use basic::util;
+
+fn main() {
+    println!("{} {}", greet(), util::shout("hi"));
+}
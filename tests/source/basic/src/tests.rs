@@ -0,0 +1,6 @@
+use super::greet;
+
+#[test]
+fn greet_shouts() {
+    assert_eq!(greet(), "hello!");
+}
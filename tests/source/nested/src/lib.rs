@@ -0,0 +1,10 @@
+mod sub;
+pub use sub::Thing;
+
+pub mod outer {
+    pub mod inner;
+}
+
+pub mod probs {
+    automod::dir!("src/probs");
+}
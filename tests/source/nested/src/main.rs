@@ -0,0 +1,15 @@
+extern crate nested;
+use nested::outer;
+use nested::probs;
+use nested::Thing;
+
+fn main() {
+    let t = Thing(5);
+    println!(
+        "{} {} {} {}",
+        t.0,
+        outer::inner::value(),
+        probs::one::one(),
+        probs::two::two()
+    );
+}